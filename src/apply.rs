@@ -0,0 +1,238 @@
+//! Server-side applying a [`ProcessedTemplate`](crate::ProcessedTemplate) to a cluster, instead
+//! of piping the rendered YAML to `kubectl`. Gated behind the `apply` feature since it pulls in
+//! `kube` and an async runtime.
+
+use std::convert::TryFrom;
+
+use kube::api::{Api, DynamicObject, Patch, PatchParams};
+use kube::config::{KubeConfigOptions, Kubeconfig};
+use kube::{Client, Config};
+use serde::Deserialize;
+
+use crate::template::ProcessedTemplate;
+
+/// Options controlling how a [`ProcessedTemplate`] is applied to a cluster.
+#[derive(Clone, Debug, Default)]
+pub struct ApplyOptions {
+    /// Overrides the namespace of every object that doesn't already declare one.
+    pub namespace: Option<String>,
+    /// Performs a server-side dry run instead of persisting the change.
+    pub dry_run: bool,
+    /// The field manager name to apply as, defaulting to `"ktmpl"`.
+    pub field_manager: Option<String>,
+    /// Path to a kubeconfig file. When `None`, the usual `KUBECONFIG`/`~/.kube/config`
+    /// resolution is used.
+    pub kubeconfig_path: Option<String>,
+}
+
+/// The outcome of applying a single object from a [`ProcessedTemplate`].
+#[derive(Clone, Debug)]
+pub struct ApplyResult {
+    /// The object's `kind`.
+    pub kind: String,
+    /// The object's `metadata.name`.
+    pub name: String,
+    /// The namespace the object was applied into, if any.
+    pub namespace: Option<String>,
+}
+
+impl ProcessedTemplate {
+    /// Parses this template's rendered YAML documents and server-side applies each object to a
+    /// cluster, returning one [`ApplyResult`] per object in document order.
+    pub async fn apply(&self, opts: ApplyOptions) -> Result<Vec<ApplyResult>, String> {
+        let kubeconfig = match &opts.kubeconfig_path {
+            Some(path) => read_kubeconfig(path)?,
+            None => Kubeconfig::read().map_err(|e| format!("could not read kubeconfig: {}", e))?,
+        };
+        let config = Config::from_custom_kubeconfig(kubeconfig, &KubeConfigOptions::default())
+            .await
+            .map_err(|e| format!("could not build client config: {}", e))?;
+        let client =
+            Client::try_from(config).map_err(|e| format!("could not build client: {}", e))?;
+
+        let field_manager = opts.field_manager.clone().unwrap_or_else(|| "ktmpl".to_string());
+        let mut results = Vec::new();
+
+        for object in rendered_objects(self)? {
+            let kind = object
+                .types
+                .as_ref()
+                .map(|t| t.kind.clone())
+                .unwrap_or_default();
+            let name = object.metadata.name.clone().unwrap_or_default();
+            let namespace = object
+                .metadata
+                .namespace
+                .clone()
+                .or_else(|| opts.namespace.clone());
+
+            let api: Api<DynamicObject> = match &namespace {
+                Some(ns) => Api::namespaced_with(client.clone(), ns, &api_resource(&object)?),
+                None => Api::all_with(client.clone(), &api_resource(&object)?),
+            };
+            let mut params = PatchParams::apply(&field_manager);
+
+            if opts.dry_run {
+                params = params.dry_run();
+            }
+
+            api.patch(&name, &params, &Patch::Apply(&object))
+                .await
+                .map_err(|e| format!("could not apply {} `{}`: {}", kind, name, e))?;
+
+            results.push(ApplyResult {
+                kind,
+                name,
+                namespace,
+            });
+        }
+
+        Ok(results)
+    }
+}
+
+/// Parses a rendered template's `---`-delimited YAML documents into objects, the same way
+/// [`Template::new_multi_with_engine`](crate::Template::new_multi_with_engine) does, instead of
+/// naively splitting on the literal substring `"---"` — which would also cut through any
+/// document whose string data happens to contain it.
+fn rendered_objects(rendered: &str) -> Result<Vec<DynamicObject>, String> {
+    let mut objects = Vec::new();
+
+    for doc in serde_yaml::Deserializer::from_str(rendered) {
+        let value = serde_yaml::Value::deserialize(doc)
+            .map_err(|e| format!("could not parse rendered object: {}", e))?;
+
+        if value.is_null() {
+            continue;
+        }
+
+        let object: DynamicObject = serde_yaml::from_value(value)
+            .map_err(|e| format!("could not parse rendered object: {}", e))?;
+
+        objects.push(object);
+    }
+
+    Ok(objects)
+}
+
+fn api_resource(object: &DynamicObject) -> Result<kube::discovery::ApiResource, String> {
+    let types = object
+        .types
+        .as_ref()
+        .ok_or_else(|| "rendered object is missing `apiVersion`/`kind`".to_string())?;
+
+    Ok(kube::discovery::ApiResource::from_gvk(&kube::core::GroupVersionKind::try_from(types).map_err(
+        |e| format!("could not determine object's group/version/kind: {}", e),
+    )?))
+}
+
+/// Reads a kubeconfig file that may contain several `---`-delimited `Config` YAML documents
+/// (common with merged/multi-context setups), concatenating their clusters, contexts, and users
+/// into a single [`Kubeconfig`]. The `current-context` of the last document that declares one
+/// wins.
+fn read_kubeconfig(path: &str) -> Result<Kubeconfig, String> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| format!("could not read kubeconfig `{}`: {}", path, e))?;
+
+    let mut merged = Kubeconfig::default();
+
+    for doc in serde_yaml::Deserializer::from_str(&contents) {
+        let mut config = Kubeconfig::deserialize(doc)
+            .map_err(|e| format!("could not parse kubeconfig document in `{}`: {}", path, e))?;
+
+        merged.clusters.append(&mut config.clusters);
+        merged.contexts.append(&mut config.contexts);
+        merged.auth_infos.append(&mut config.auth_infos);
+
+        if config.current_context.is_some() {
+            merged.current_context = config.current_context;
+        }
+    }
+
+    Ok(merged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rendered_objects_does_not_split_on_embedded_dashes() {
+        let rendered = r#"---
+apiVersion: v1
+kind: ConfigMap
+metadata:
+  name: example
+data:
+  note: "before---after"
+"#;
+
+        let objects = rendered_objects(rendered).unwrap();
+
+        assert_eq!(objects.len(), 1);
+        assert_eq!(objects[0].metadata.name, Some("example".to_string()));
+    }
+
+    #[test]
+    fn rendered_objects_splits_multiple_documents() {
+        let rendered = r#"---
+apiVersion: v1
+kind: Namespace
+metadata:
+  name: first
+---
+apiVersion: v1
+kind: Namespace
+metadata:
+  name: second
+"#;
+
+        let objects = rendered_objects(rendered).unwrap();
+
+        assert_eq!(objects.len(), 2);
+        assert_eq!(objects[0].metadata.name, Some("first".to_string()));
+        assert_eq!(objects[1].metadata.name, Some("second".to_string()));
+    }
+
+    #[test]
+    fn read_kubeconfig_merges_multiple_documents() {
+        let contents = r#"
+apiVersion: v1
+kind: Config
+clusters:
+  - name: staging
+    cluster:
+      server: https://staging.example.com
+contexts:
+  - name: staging
+    context:
+      cluster: staging
+      user: staging
+current-context: staging
+---
+apiVersion: v1
+kind: Config
+clusters:
+  - name: prod
+    cluster:
+      server: https://prod.example.com
+contexts:
+  - name: prod
+    context:
+      cluster: prod
+      user: prod
+current-context: prod
+"#;
+        let path = std::env::temp_dir().join("ktmpl_read_kubeconfig_merges_multiple_documents.yml");
+
+        std::fs::write(&path, contents).unwrap();
+
+        let merged = read_kubeconfig(path.to_str().unwrap()).unwrap();
+
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(merged.clusters.len(), 2);
+        assert_eq!(merged.contexts.len(), 2);
+        assert_eq!(merged.current_context, Some("prod".to_string()));
+    }
+}
@@ -74,16 +74,27 @@
 
 pub use crate::{
     parameter::{
+        merge_parameter_values,
+        parameter_values_from_dotenv,
+        parameter_values_from_dotenv_str,
+        parameter_values_from_env,
         parameter_values_from_file,
+        parameter_values_from_layers,
+        parameter_values_from_sources,
         parameter_values_from_str,
         parameter_values_from_yaml,
+        Parameter,
         ParameterValue,
         ParameterValues,
     },
-    secret::{Secret, Secrets},
-    template::Template,
+    secret::{EnvSecretProvider, Secret, SecretProvider, SecretProviders, Secrets},
+    template::{ProcessedTemplate, RenderMode, Template},
 };
+#[cfg(feature = "apply")]
+pub use crate::apply::{ApplyOptions, ApplyResult};
 
+#[cfg(feature = "apply")]
+mod apply;
 mod parameter;
 mod processor;
 mod secret;
@@ -95,10 +106,18 @@ mod tests {
     use std::io::Read;
 
     use super::{
+        merge_parameter_values,
+        parameter_values_from_dotenv_str,
+        parameter_values_from_env,
         parameter_values_from_file,
+        parameter_values_from_layers,
+        parameter_values_from_sources,
+        EnvSecretProvider,
         ParameterValue,
         ParameterValues,
+        RenderMode,
         Secret,
+        SecretProviders,
         Secrets,
         Template,
     };
@@ -198,6 +217,69 @@ type: Opaque"#
         );
     }
 
+    #[test]
+    fn string_data_is_not_encoded() {
+        let template_contents = r#"
+---
+kind: "Template"
+apiVersion: "v1"
+metadata:
+  name: "example"
+objects:
+  - kind: "Secret"
+    apiVersion: "v1"
+    metadata:
+      name: "webapp"
+    stringData:
+      password: "$(PASSWORD)"
+    type: "Opaque"
+parameters:
+  - name: "PASSWORD"
+    description: "The password for the web app"
+    required: true
+    parameterType: "string"
+"#;
+
+        let mut parameter_values = ParameterValues::new();
+
+        parameter_values.insert(
+            "PASSWORD".to_string(),
+            ParameterValue::Plain("narble".to_string()),
+        );
+
+        let mut secrets = Secrets::new();
+
+        secrets.insert(Secret {
+            name: "webapp".to_string(),
+            namespace: "default".to_string(),
+        });
+
+        let template = Template::new(
+            template_contents.to_string(),
+            parameter_values,
+            Some(secrets),
+        )
+        .unwrap();
+
+        let processed_template = template.process().unwrap();
+
+        assert_eq!(
+            processed_template
+                .lines()
+                .map(|l| l.trim_end())
+                .collect::<Vec<&str>>()
+                .join("\n"),
+            r#"---
+kind: Secret
+apiVersion: v1
+metadata:
+  name: webapp
+stringData:
+  password: narble
+type: Opaque"#
+        );
+    }
+
     #[test]
     fn missing_secret() {
         let template_contents = r#"
@@ -242,6 +324,480 @@ parameters:
         assert!(template.process().is_err());
     }
 
+    #[test]
+    fn jinja_conditionals_and_loops() {
+        let template_contents = r#"
+---
+objects:
+    - kind: "ConfigMap"
+      metadata:
+        name: "example"
+      data:
+        greeting: "{{ GREETING | default('hi') | upper }}"
+{% if DEBUG %}
+        debug: "true"
+{% endif %}
+        users: {{ USERS }}
+parameters:
+  - name: "GREETING"
+    required: false
+  - name: "DEBUG"
+    required: false
+  - name: "USERS"
+    required: true
+"#;
+
+        let mut parameter_values = ParameterValues::new();
+
+        parameter_values.insert(
+            "DEBUG".to_string(),
+            ParameterValue::Plain("true".to_string()),
+        );
+        parameter_values.insert(
+            "USERS".to_string(),
+            ParameterValue::List(vec!["carl".to_string(), "sal".to_string()]),
+        );
+
+        let template = Template::new_with_engine(
+            template_contents.to_string(),
+            parameter_values,
+            None,
+            RenderMode::Jinja,
+        )
+        .unwrap();
+
+        let processed_template = template.process().unwrap();
+
+        assert_eq!(
+            processed_template
+                .lines()
+                .map(|l| l.trim_end())
+                .collect::<Vec<&str>>()
+                .join("\n"),
+            r#"---
+kind: ConfigMap
+metadata:
+  name: example
+data:
+  greeting: HI
+  debug: 'true'
+  users:
+  - carl
+  - sal"#
+        );
+    }
+
+    #[test]
+    fn jinja_renders_declared_parameter_default() {
+        let template_contents = r#"
+---
+objects:
+    - kind: "ConfigMap"
+      metadata:
+        name: "example"
+      data:
+        greeting: "{{ GREETING }}"
+parameters:
+  - name: "GREETING"
+    value: "hello-default"
+"#;
+
+        let template = Template::new_with_engine(
+            template_contents.to_string(),
+            ParameterValues::new(),
+            None,
+            RenderMode::Jinja,
+        )
+        .unwrap();
+
+        let processed_template = template.process().unwrap();
+
+        assert_eq!(
+            processed_template
+                .lines()
+                .map(|l| l.trim_end())
+                .collect::<Vec<&str>>()
+                .join("\n"),
+            r#"---
+kind: ConfigMap
+metadata:
+  name: example
+data:
+  greeting: hello-default"#
+        );
+    }
+
+    #[test]
+    fn declared_parameters_discovers_names_before_values_are_known() {
+        let template_contents = r#"
+---
+objects:
+    - kind: "Namespace"
+      metadata:
+        name: "$(NAMESPACE)"
+parameters:
+  - name: "NAMESPACE"
+    required: true
+    value: "default"
+"#;
+
+        let declared = Template::declared_parameters(template_contents);
+
+        assert_eq!(declared.len(), 1);
+        assert_eq!(declared[0].name, "NAMESPACE");
+        assert_eq!(declared[0].value, Some("default".to_string()));
+    }
+
+    #[test]
+    fn parameter_values_from_sources_applies_precedence() {
+        std::env::set_var("KTMPL_FROM_ENV", "from-env");
+
+        let contents = "FROM_DOTENV=from-dotenv\n";
+        let path = std::env::temp_dir().join("ktmpl_parameter_values_from_sources.env");
+
+        std::fs::write(&path, contents).unwrap();
+
+        let declared = Template::declared_parameters(
+            r#"
+---
+objects: []
+parameters:
+  - name: "FROM_DEFAULT"
+    value: "from-default"
+  - name: "FROM_DOTENV"
+  - name: "FROM_ENV"
+  - name: "FROM_EXPLICIT"
+    value: "overridden-default"
+"#,
+        );
+
+        let mut explicit = ParameterValues::new();
+
+        explicit.insert(
+            "FROM_EXPLICIT".to_string(),
+            ParameterValue::Plain("from-explicit".to_string()),
+        );
+
+        let values = parameter_values_from_sources(
+            &declared,
+            explicit,
+            Some("KTMPL_"),
+            Some(path.to_str().unwrap()),
+        )
+        .unwrap();
+
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            values.get("FROM_DEFAULT"),
+            Some(&ParameterValue::Plain("from-default".to_string()))
+        );
+        assert_eq!(
+            values.get("FROM_DOTENV"),
+            Some(&ParameterValue::Plain("from-dotenv".to_string()))
+        );
+        assert_eq!(
+            values.get("FROM_ENV"),
+            Some(&ParameterValue::Plain("from-env".to_string()))
+        );
+        assert_eq!(
+            values.get("FROM_EXPLICIT"),
+            Some(&ParameterValue::Plain("from-explicit".to_string()))
+        );
+    }
+
+    #[test]
+    fn resolve_secret_from_provider() {
+        std::env::set_var("KTMPL_TEST_PASSWORD", "narble");
+
+        let template_contents = r#"
+---
+kind: "Template"
+apiVersion: "v1"
+metadata:
+  name: "example"
+objects:
+  - kind: "Secret"
+    apiVersion: "v1"
+    metadata:
+      name: "webapp"
+    data:
+      password: "env://KTMPL_TEST_PASSWORD"
+    type: "Opaque"
+parameters: []
+"#;
+
+        let mut secrets = Secrets::new();
+
+        secrets.insert(Secret {
+            name: "webapp".to_string(),
+            namespace: "default".to_string(),
+        });
+
+        let mut providers = SecretProviders::new();
+
+        providers.register(Box::new(EnvSecretProvider));
+
+        let template = Template::new(
+            template_contents.to_string(),
+            ParameterValues::new(),
+            Some(secrets),
+        )
+        .unwrap()
+        .with_secret_providers(providers);
+
+        let processed_template = template.process().unwrap();
+
+        assert_eq!(
+            processed_template
+                .lines()
+                .map(|l| l.trim_end())
+                .collect::<Vec<&str>>()
+                .join("\n"),
+            r#"---
+kind: Secret
+apiVersion: v1
+metadata:
+  name: webapp
+data:
+  password: bmFyYmxl
+type: Opaque"#
+        );
+    }
+
+    #[test]
+    fn provider_refs_are_not_resolved_outside_secret_objects() {
+        std::env::set_var("KTMPL_TEST_CONFIG_VALUE", "narble");
+
+        let template_contents = r#"
+---
+kind: "Template"
+apiVersion: "v1"
+metadata:
+  name: "example"
+objects:
+  - kind: "ConfigMap"
+    apiVersion: "v1"
+    metadata:
+      name: "webapp"
+    data:
+      reference: "env://KTMPL_TEST_CONFIG_VALUE"
+parameters: []
+"#;
+
+        let mut providers = SecretProviders::new();
+
+        providers.register(Box::new(EnvSecretProvider));
+
+        let template = Template::new(
+            template_contents.to_string(),
+            ParameterValues::new(),
+            None,
+        )
+        .unwrap()
+        .with_secret_providers(providers);
+
+        let processed_template = template.process().unwrap();
+
+        assert_eq!(
+            processed_template
+                .lines()
+                .map(|l| l.trim_end())
+                .collect::<Vec<&str>>()
+                .join("\n"),
+            r#"---
+kind: ConfigMap
+apiVersion: v1
+metadata:
+  name: webapp
+data:
+  reference: env://KTMPL_TEST_CONFIG_VALUE"#
+        );
+    }
+
+    #[test]
+    fn parameter_values_from_env_with_prefix() {
+        std::env::set_var("KTMPL_DATABASE_SERVICE_NAME", "mongo");
+
+        let values = parameter_values_from_env(vec!["DATABASE_SERVICE_NAME"], Some("KTMPL_"));
+
+        assert_eq!(
+            values.get("DATABASE_SERVICE_NAME"),
+            Some(&ParameterValue::Plain("mongo".to_string()))
+        );
+    }
+
+    #[test]
+    fn parameter_values_from_dotenv() {
+        let contents = r#"
+# comment
+DATABASE_SERVICE_NAME=mongo
+PASSWORD="narble narble"
+export EMPTY=
+"#;
+
+        let values = parameter_values_from_dotenv_str(contents).unwrap();
+
+        assert_eq!(
+            values.get("DATABASE_SERVICE_NAME"),
+            Some(&ParameterValue::Plain("mongo".to_string()))
+        );
+        assert_eq!(
+            values.get("PASSWORD"),
+            Some(&ParameterValue::Plain("narble narble".to_string()))
+        );
+        assert_eq!(values.get("EMPTY"), Some(&ParameterValue::Plain(String::new())));
+    }
+
+    #[test]
+    fn merge_parameter_values_overlay_wins() {
+        let mut base = ParameterValues::new();
+
+        base.insert(
+            "REGION".to_string(),
+            ParameterValue::Plain("us-east-1".to_string()),
+        );
+        base.insert(
+            "REPLICAS".to_string(),
+            ParameterValue::Plain("1".to_string()),
+        );
+
+        let mut overlay = ParameterValues::new();
+
+        overlay.insert(
+            "REGION".to_string(),
+            ParameterValue::Plain("eu-west-1".to_string()),
+        );
+
+        merge_parameter_values(&mut base, overlay);
+
+        assert_eq!(
+            base.get("REGION"),
+            Some(&ParameterValue::Plain("eu-west-1".to_string()))
+        );
+        assert_eq!(
+            base.get("REPLICAS"),
+            Some(&ParameterValue::Plain("1".to_string()))
+        );
+    }
+
+    #[test]
+    fn parameter_values_from_layers_selects_matching_overlay() {
+        let dir = std::env::temp_dir().join("ktmpl_parameter_values_from_layers");
+
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let base_path = dir.join("params.yml");
+        let staging_path = dir.join("staging.yml");
+        let prod_path = dir.join("prod.yml");
+
+        std::fs::write(
+            &base_path,
+            r#"
+- name: REGION
+  value: us-east-1
+- name: REPLICAS
+  value: "1"
+"#,
+        )
+        .unwrap();
+        std::fs::write(
+            &staging_path,
+            r#"
+- name: REGION
+  value: us-west-2
+"#,
+        )
+        .unwrap();
+        std::fs::write(
+            &prod_path,
+            r#"
+- name: REGION
+  value: eu-west-1
+"#,
+        )
+        .unwrap();
+
+        let overlay_paths = [staging_path.to_str().unwrap(), prod_path.to_str().unwrap()];
+
+        let prod_values =
+            parameter_values_from_layers(base_path.to_str().unwrap(), &overlay_paths, "prod")
+                .unwrap();
+
+        let staging_values =
+            parameter_values_from_layers(base_path.to_str().unwrap(), &overlay_paths, "staging")
+                .unwrap();
+
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            prod_values.get("REGION"),
+            Some(&ParameterValue::Plain("eu-west-1".to_string()))
+        );
+        assert_eq!(
+            prod_values.get("REPLICAS"),
+            Some(&ParameterValue::Plain("1".to_string()))
+        );
+        assert_eq!(
+            staging_values.get("REGION"),
+            Some(&ParameterValue::Plain("us-west-2".to_string()))
+        );
+    }
+
+    #[test]
+    fn multi_document_template_with_passthrough() {
+        let template_contents = r#"
+---
+kind: "Template"
+apiVersion: "v1"
+metadata:
+  name: "example"
+objects:
+  - kind: "Service"
+    apiVersion: "v1"
+    metadata:
+      name: "$(DATABASE_SERVICE_NAME)"
+parameters:
+  - name: "DATABASE_SERVICE_NAME"
+    required: true
+---
+kind: "Namespace"
+apiVersion: "v1"
+metadata:
+  name: "mongo"
+"#;
+
+        let mut parameter_values = ParameterValues::new();
+
+        parameter_values.insert(
+            "DATABASE_SERVICE_NAME".to_string(),
+            ParameterValue::Plain("mongo".to_string()),
+        );
+
+        let template =
+            Template::new_multi(template_contents.to_string(), parameter_values, None).unwrap();
+
+        let processed_template = template.process().unwrap();
+
+        assert_eq!(
+            processed_template
+                .lines()
+                .map(|l| l.trim_end())
+                .collect::<Vec<&str>>()
+                .join("\n"),
+            r#"---
+kind: Service
+apiVersion: v1
+metadata:
+  name: mongo
+---
+kind: Namespace
+apiVersion: v1
+metadata:
+  name: mongo"#
+        );
+    }
+
     #[test]
     fn parameter_file() {
         let mut template_file = File::open("example.yml").unwrap();
@@ -0,0 +1,252 @@
+//! Parameter declarations and the values supplied for them when processing a template.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+
+use serde::Deserialize;
+use serde_yaml::Value;
+
+/// The value supplied for a single parameter.
+///
+/// Most parameters are simple strings, but the `list` and `map` parameter types let a template
+/// generate repeated blocks of YAML (via `{% for %}` in [`RenderMode::Jinja`](crate::RenderMode))
+/// from structured data instead of a single scalar.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ParameterValue {
+    /// A plain string value.
+    Plain(String),
+    /// A list of string values.
+    List(Vec<String>),
+    /// A map of string keys to string values.
+    Map(HashMap<String, String>),
+}
+
+/// A mapping of parameter names to the values that should be substituted for them.
+pub type ParameterValues = HashMap<String, ParameterValue>;
+
+/// A parameter declared by a `Template`'s `parameters` list.
+#[derive(Clone, Debug, Deserialize)]
+pub struct Parameter {
+    /// The name referenced by `$(NAME)` and `{{ NAME }}` in the template.
+    pub name: String,
+    /// A human readable description of the parameter.
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Whether a value must be supplied for this parameter.
+    #[serde(default)]
+    pub required: bool,
+    /// The default value to use when no value is supplied.
+    #[serde(default)]
+    pub value: Option<String>,
+    /// The declared type of the parameter (`string`, `list`, or `map`).
+    #[serde(rename = "parameterType", default)]
+    pub parameter_type: Option<String>,
+}
+
+/// Reads parameter values from a YAML file at `path`.
+pub fn parameter_values_from_file(path: &str) -> Result<ParameterValues, String> {
+    let mut file =
+        File::open(path).map_err(|e| format!("could not open parameter file `{}`: {}", path, e))?;
+    let mut contents = String::new();
+
+    file.read_to_string(&mut contents)
+        .map_err(|e| format!("could not read parameter file `{}`: {}", path, e))?;
+
+    parameter_values_from_str(&contents)
+}
+
+/// Parses parameter values from a YAML string.
+pub fn parameter_values_from_str(contents: &str) -> Result<ParameterValues, String> {
+    let value: Value = serde_yaml::from_str(contents)
+        .map_err(|e| format!("could not parse parameter YAML: {}", e))?;
+
+    parameter_values_from_yaml(value)
+}
+
+/// Builds parameter values from an already-parsed YAML value of the form
+/// `[{name: NAME, value: VALUE}, ...]`.
+pub fn parameter_values_from_yaml(value: Value) -> Result<ParameterValues, String> {
+    #[derive(Deserialize)]
+    struct RawParameterValue {
+        name: String,
+        value: String,
+    }
+
+    let raw: Vec<RawParameterValue> =
+        serde_yaml::from_value(value).map_err(|e| format!("could not parse parameter values: {}", e))?;
+
+    Ok(raw
+        .into_iter()
+        .map(|p| (p.name, ParameterValue::Plain(p.value)))
+        .collect())
+}
+
+/// Reads parameter values from environment variables, one per name in `names`.
+///
+/// When `prefix` is given (e.g. `Some("KTMPL_")`), the variable looked up for a parameter named
+/// `NAME` is `KTMPL_NAME` rather than `NAME`. Parameters with no matching environment variable are
+/// simply absent from the result, so this is meant to be layered underneath explicit values and
+/// on top of a dotenv file or a parameter's own default. [`parameter_values_from_sources`] does
+/// this layering for you, driven by a template's declared parameters.
+pub fn parameter_values_from_env<'a>(
+    names: impl IntoIterator<Item = &'a str>,
+    prefix: Option<&str>,
+) -> ParameterValues {
+    names
+        .into_iter()
+        .filter_map(|name| {
+            let var_name = match prefix {
+                Some(prefix) => format!("{}{}", prefix, name),
+                None => name.to_string(),
+            };
+
+            std::env::var(var_name)
+                .ok()
+                .map(|value| (name.to_string(), ParameterValue::Plain(value)))
+        })
+        .collect()
+}
+
+/// Reads parameter values from a dotenv-style file at `path`: `KEY=value` lines, with support
+/// for `#` comments, blank lines, and single- or double-quoted values.
+pub fn parameter_values_from_dotenv(path: &str) -> Result<ParameterValues, String> {
+    let mut file =
+        File::open(path).map_err(|e| format!("could not open dotenv file `{}`: {}", path, e))?;
+    let mut contents = String::new();
+
+    file.read_to_string(&mut contents)
+        .map_err(|e| format!("could not read dotenv file `{}`: {}", path, e))?;
+
+    parameter_values_from_dotenv_str(&contents)
+}
+
+/// Parses parameter values from dotenv-formatted text, as read by
+/// [`parameter_values_from_dotenv`].
+pub fn parameter_values_from_dotenv_str(contents: &str) -> Result<ParameterValues, String> {
+    let mut values = ParameterValues::new();
+
+    for (i, line) in contents.lines().enumerate() {
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| format!("dotenv line {} is not in `KEY=value` form", i + 1))?;
+        let key = key.trim();
+        let value = unquote_dotenv_value(value.trim());
+
+        values.insert(key.to_string(), ParameterValue::Plain(value));
+    }
+
+    Ok(values)
+}
+
+/// Loads `base_path` as the default parameter values, then deep-merges on top of it, in order,
+/// each of `overlay_paths` whose filename stem matches `active_env`. Overlays whose stem names a
+/// different environment are not read.
+///
+/// A merged overlay's keys win over the base and earlier merged overlays; list/map parameter values are
+/// merged by whole-value replacement rather than element-wise. This lets one base parameter file
+/// be shared across environments (or regions) with only the differing values declared in each
+/// overlay, e.g.:
+///
+/// ```ignore
+/// let values = parameter_values_from_layers(
+///     "params.yml",
+///     &["environments/staging.yml", "environments/prod.yml"],
+///     "prod",
+/// )?;
+/// ```
+pub fn parameter_values_from_layers(
+    base_path: &str,
+    overlay_paths: &[&str],
+    active_env: &str,
+) -> Result<ParameterValues, String> {
+    let mut values = parameter_values_from_file(base_path)?;
+
+    for overlay_path in overlay_paths {
+        if overlay_stem_matches(overlay_path, active_env) {
+            let overlay = parameter_values_from_file(overlay_path)?;
+
+            merge_parameter_values(&mut values, overlay);
+        }
+    }
+
+    Ok(values)
+}
+
+/// Resolves values for `parameters` by layering, from lowest to highest precedence: each
+/// parameter's own declared default, a dotenv file, environment variables, and finally
+/// `explicit` (e.g. values a caller supplied directly).
+///
+/// `parameters` is typically obtained from
+/// [`Template::declared_parameters`](crate::Template::declared_parameters), which means a caller
+/// no longer needs to already know every parameter name up front to apply this precedence:
+///
+/// ```ignore
+/// let declared = Template::declared_parameters(&template_contents);
+/// let values = parameter_values_from_sources(
+///     &declared,
+///     explicit_values,
+///     Some("KTMPL_"),
+///     Some(".env"),
+/// )?;
+/// ```
+pub fn parameter_values_from_sources(
+    parameters: &[Parameter],
+    explicit: ParameterValues,
+    env_prefix: Option<&str>,
+    dotenv_path: Option<&str>,
+) -> Result<ParameterValues, String> {
+    let mut values: ParameterValues = parameters
+        .iter()
+        .filter_map(|parameter| {
+            parameter
+                .value
+                .clone()
+                .map(|value| (parameter.name.clone(), ParameterValue::Plain(value)))
+        })
+        .collect();
+
+    if let Some(path) = dotenv_path {
+        merge_parameter_values(&mut values, parameter_values_from_dotenv(path)?);
+    }
+
+    let names: Vec<&str> = parameters.iter().map(|parameter| parameter.name.as_str()).collect();
+
+    merge_parameter_values(&mut values, parameter_values_from_env(names, env_prefix));
+    merge_parameter_values(&mut values, explicit);
+
+    Ok(values)
+}
+
+/// Deep-merges `overlay` into `base`: every key present in `overlay` replaces the corresponding
+/// entry (if any) in `base`, and keys unique to either side are kept as-is.
+pub fn merge_parameter_values(base: &mut ParameterValues, overlay: ParameterValues) {
+    for (name, value) in overlay {
+        base.insert(name, value);
+    }
+}
+
+fn overlay_stem_matches(path: &str, active_env: &str) -> bool {
+    std::path::Path::new(path)
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .map(|stem| stem == active_env)
+        .unwrap_or(false)
+}
+
+fn unquote_dotenv_value(value: &str) -> String {
+    for quote in ['"', '\''] {
+        if value.len() >= 2 && value.starts_with(quote) && value.ends_with(quote) {
+            return value[1..value.len() - 1].to_string();
+        }
+    }
+
+    value.to_string()
+}
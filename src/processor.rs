@@ -0,0 +1,146 @@
+//! Low level YAML walking used to substitute `$(PARAM)` tokens and to base64 encode `Secret`
+//! object data once a template's objects have been parsed.
+
+use std::collections::HashMap;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde_yaml::Value;
+
+use crate::secret::SecretProviders;
+
+lazy_static! {
+    static ref TOKEN: Regex = Regex::new(r"\$\(([A-Za-z0-9_]+)\)").expect("invalid token regex");
+}
+
+/// Replaces every `$(NAME)` occurrence in `value` (including mapping keys) with the
+/// corresponding entry from `params`.
+///
+/// Returns an error naming the first token whose parameter has no resolved value.
+pub fn substitute_tokens(value: &Value, params: &HashMap<String, String>) -> Result<Value, String> {
+    match value {
+        Value::String(s) => Ok(Value::String(substitute_str(s, params)?)),
+        Value::Sequence(items) => {
+            let mut out = Vec::with_capacity(items.len());
+
+            for item in items {
+                out.push(substitute_tokens(item, params)?);
+            }
+
+            Ok(Value::Sequence(out))
+        }
+        Value::Mapping(map) => {
+            let mut out = serde_yaml::Mapping::new();
+
+            for (k, v) in map {
+                let key = substitute_tokens(k, params)?;
+                let value = substitute_tokens(v, params)?;
+
+                out.insert(key, value);
+            }
+
+            Ok(Value::Mapping(out))
+        }
+        other => Ok(other.clone()),
+    }
+}
+
+/// Substitutes `$(NAME)` tokens within a single string.
+pub fn substitute_str(input: &str, params: &HashMap<String, String>) -> Result<String, String> {
+    let mut error = None;
+    let replaced = TOKEN.replace_all(input, |captures: &regex::Captures| {
+        let name = &captures[1];
+
+        match params.get(name) {
+            Some(value) => value.clone(),
+            None => {
+                error.get_or_insert_with(|| format!("no value supplied for parameter `{}`", name));
+
+                String::new()
+            }
+        }
+    });
+    let replaced = replaced.into_owned();
+
+    match error {
+        Some(e) => Err(e),
+        None => Ok(replaced),
+    }
+}
+
+/// Resolves `scheme://reference` provider references (e.g. `vault://...`) within the `data` and
+/// `stringData` fields of a `kind: Secret` object, leaving every other object, and every other
+/// field of a `Secret`, untouched.
+///
+/// Provider resolution is scoped to `Secret` objects' own data fields rather than applied to
+/// every string leaf of every object, so a plain `ConfigMap`, `Deployment` env var, or label that
+/// happens to look like `scheme://ref` isn't silently rewritten with resolved secret material
+/// into a resource that isn't base64 encoded or otherwise treated as sensitive.
+pub fn resolve_secret_provider_refs(object: &Value, providers: &SecretProviders) -> Result<Value, String> {
+    let is_secret = object.get("kind").and_then(Value::as_str) == Some("Secret");
+
+    let map = match (is_secret, object) {
+        (true, Value::Mapping(map)) => map,
+        _ => return Ok(object.clone()),
+    };
+
+    let mut out = map.clone();
+
+    for key in ["data", "stringData"] {
+        if let Some(value) = map.get(Value::from(key)) {
+            out.insert(Value::from(key), resolve_provider_refs(value, providers)?);
+        }
+    }
+
+    Ok(Value::Mapping(out))
+}
+
+/// Recursively replaces every string leaf of `value` that matches a registered secret
+/// provider's scheme (e.g. `vault://...`) with the value the provider resolves it to.
+fn resolve_provider_refs(value: &Value, providers: &SecretProviders) -> Result<Value, String> {
+    match value {
+        Value::String(s) => match providers.resolve(s)? {
+            Some(resolved) => Ok(Value::String(resolved)),
+            None => Ok(Value::String(s.clone())),
+        },
+        Value::Sequence(items) => {
+            let mut out = Vec::with_capacity(items.len());
+
+            for item in items {
+                out.push(resolve_provider_refs(item, providers)?);
+            }
+
+            Ok(Value::Sequence(out))
+        }
+        Value::Mapping(map) => {
+            let mut out = serde_yaml::Mapping::new();
+
+            for (k, v) in map {
+                out.insert(k.clone(), resolve_provider_refs(v, providers)?);
+            }
+
+            Ok(Value::Mapping(out))
+        }
+        other => Ok(other.clone()),
+    }
+}
+
+/// Recursively base64 encodes every string leaf of `value`, used to encode a `Secret` object's
+/// `data` field.
+pub fn encode_secret_data(value: &Value) -> Value {
+    match value {
+        Value::String(s) => Value::String(STANDARD.encode(s)),
+        Value::Sequence(items) => Value::Sequence(items.iter().map(encode_secret_data).collect()),
+        Value::Mapping(map) => {
+            let mut out = serde_yaml::Mapping::new();
+
+            for (k, v) in map {
+                out.insert(k.clone(), encode_secret_data(v));
+            }
+
+            Value::Mapping(out)
+        }
+        other => other.clone(),
+    }
+}
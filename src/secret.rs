@@ -0,0 +1,92 @@
+//! Types describing which objects in a template are Kubernetes `Secret`s so their `data` can be
+//! base64 encoded when the template is processed, and the [`SecretProvider`] subsystem used to
+//! resolve secret values from external stores instead of inlining them.
+//!
+//! `stringData` is left untouched: it's the Kubernetes API's own plain-text convenience field,
+//! which the API server base64-encodes into `data` on admission, so encoding it here would
+//! double-encode the value.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+/// A `Secret` object present in a template, identified by its `kind: Secret` and
+/// `metadata.name`/`metadata.namespace`.
+///
+/// Registering a `Secret` tells [`Template::process`](crate::Template::process) that the
+/// matching object's `data` values should be base64 encoded rather than left as plain text.
+/// `stringData` is never encoded, since the Kubernetes API server already does that on admission.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Secret {
+    /// The `metadata.name` of the `Secret` object.
+    pub name: String,
+    /// The `metadata.namespace` of the `Secret` object.
+    pub namespace: String,
+}
+
+/// A set of [`Secret`]s to look for among a template's objects.
+pub type Secrets = HashSet<Secret>;
+
+/// A source of secret values resolved by reference when a template is processed, e.g.
+/// `vault://secret/data/webapp#password`.
+///
+/// Implementations are registered with [`Template::with_secret_providers`](crate::Template::with_secret_providers)
+/// under the URI scheme they handle; a parameter value or a `Secret` object's `data`/
+/// `stringData` field written as `scheme://reference` is replaced with the result of
+/// `resolve("reference")` before the existing base64 encoding pass runs.
+pub trait SecretProvider: fmt::Debug {
+    /// The URI scheme this provider handles, e.g. `"vault"` for `vault://...` references.
+    fn scheme(&self) -> &str;
+
+    /// Resolves `reference` (the part of the value after `scheme://`) to its real value.
+    fn resolve(&self, reference: &str) -> Result<String, String>;
+}
+
+/// A registry of [`SecretProvider`]s, keyed by the URI scheme they handle.
+#[derive(Debug, Default)]
+pub struct SecretProviders {
+    providers: HashMap<String, Box<dyn SecretProvider>>,
+}
+
+impl SecretProviders {
+    /// Creates an empty registry.
+    pub fn new() -> SecretProviders {
+        SecretProviders::default()
+    }
+
+    /// Registers `provider`, replacing any existing provider for the same scheme.
+    pub fn register(&mut self, provider: Box<dyn SecretProvider>) {
+        self.providers.insert(provider.scheme().to_string(), provider);
+    }
+
+    /// Resolves `value` if it is of the form `scheme://reference` for a registered scheme.
+    ///
+    /// Returns `Ok(None)` when `value` does not match any registered scheme, leaving it to be
+    /// used as-is.
+    pub fn resolve(&self, value: &str) -> Result<Option<String>, String> {
+        let (scheme, reference) = match value.split_once("://") {
+            Some(parts) => parts,
+            None => return Ok(None),
+        };
+
+        match self.providers.get(scheme) {
+            Some(provider) => provider.resolve(reference).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+/// A built-in [`SecretProvider`] that resolves `env://NAME` references from the current
+/// process's environment variables.
+#[derive(Debug, Default)]
+pub struct EnvSecretProvider;
+
+impl SecretProvider for EnvSecretProvider {
+    fn scheme(&self) -> &str {
+        "env"
+    }
+
+    fn resolve(&self, reference: &str) -> Result<String, String> {
+        std::env::var(reference)
+            .map_err(|_| format!("environment variable `{}` is not set", reference))
+    }
+}
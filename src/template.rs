@@ -0,0 +1,421 @@
+//! The [`Template`] type: parses a Kubernetes template document, resolves its parameters, and
+//! renders the finished object manifests.
+
+use std::collections::HashMap;
+
+use minijinja::value::Value as JinjaValue;
+use minijinja::{Environment, UndefinedBehavior};
+use regex::Regex;
+use serde::Deserialize;
+use serde_yaml::Value;
+
+use crate::parameter::{Parameter, ParameterValue, ParameterValues};
+use crate::processor::{encode_secret_data, resolve_secret_provider_refs, substitute_str, substitute_tokens};
+use crate::secret::{SecretProviders, Secrets};
+
+#[derive(Debug, Deserialize)]
+struct RawTemplate {
+    objects: Vec<Value>,
+    #[serde(default)]
+    parameters: Vec<Parameter>,
+}
+
+/// Controls how `$(...)` and `{{ }}` / `{% %}` constructs in a template are rendered.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum RenderMode {
+    /// Only the classic `$(PARAM)` token substitution is performed.
+    Classic,
+    /// `$(PARAM)` tokens are expanded first, then the result is rendered through a MiniJinja
+    /// environment that understands `{{ NAME }}` interpolation, `{% if %}` / `{% for %}` blocks,
+    /// and filters such as `| default("x")`, `| upper`, and `| b64encode`.
+    Jinja,
+}
+
+/// A single YAML document from a template file: either a `kind: Template` document to be
+/// resolved and rendered, or any other document, which is passed through untouched.
+#[derive(Debug)]
+enum Document {
+    Template(RawTemplate),
+    Passthrough(Value),
+}
+
+/// A parsed Kubernetes template, ready to be [`process`](Template::process)ed into finished
+/// object manifests.
+#[derive(Debug)]
+pub struct Template {
+    documents: Vec<Document>,
+    params: HashMap<String, String>,
+    secrets: Secrets,
+    secret_providers: SecretProviders,
+}
+
+impl Template {
+    /// Parses `contents` the same way as [`Template::new_multi`] (a single template document, or
+    /// a `---`-delimited stream of documents), using the classic `$(PARAM)` substitution syntax.
+    pub fn new(
+        contents: String,
+        parameter_values: ParameterValues,
+        secrets: Option<Secrets>,
+    ) -> Result<Template, String> {
+        Template::new_with_engine(contents, parameter_values, secrets, RenderMode::Classic)
+    }
+
+    /// Parses `contents` the same way as [`Template::new_multi_with_engine`], rendering it with
+    /// the given [`RenderMode`].
+    pub fn new_with_engine(
+        contents: String,
+        parameter_values: ParameterValues,
+        secrets: Option<Secrets>,
+        mode: RenderMode,
+    ) -> Result<Template, String> {
+        Template::new_multi_with_engine(contents, parameter_values, secrets, mode)
+    }
+
+    /// Parses `contents` as a `---`-delimited stream of YAML documents. Every document whose
+    /// `kind` is `Template` (or that has no `kind` at all, for backwards compatibility with
+    /// single, bare template documents) is parameterized with the shared `parameter_values`/
+    /// `secrets`; every other document is passed through to [`process`](Template::process)
+    /// unchanged, in its original position.
+    pub fn new_multi(
+        contents: String,
+        parameter_values: ParameterValues,
+        secrets: Option<Secrets>,
+    ) -> Result<Template, String> {
+        Template::new_multi_with_engine(contents, parameter_values, secrets, RenderMode::Classic)
+    }
+
+    /// Like [`Template::new_multi`], but rendering `contents` with the given [`RenderMode`]
+    /// before splitting it into documents.
+    pub fn new_multi_with_engine(
+        contents: String,
+        parameter_values: ParameterValues,
+        secrets: Option<Secrets>,
+        mode: RenderMode,
+    ) -> Result<Template, String> {
+        let contents = match mode {
+            RenderMode::Classic => contents,
+            RenderMode::Jinja => render_jinja(&contents, &parameter_values)?,
+        };
+
+        let mut documents = Vec::new();
+
+        for raw_doc in serde_yaml::Deserializer::from_str(&contents) {
+            let value = Value::deserialize(raw_doc)
+                .map_err(|e| format!("could not parse template YAML: {}", e))?;
+
+            if value.is_null() {
+                continue;
+            }
+
+            let kind = value.get("kind").and_then(Value::as_str);
+
+            documents.push(match kind {
+                None | Some("Template") => {
+                    let raw: RawTemplate = serde_yaml::from_value(value)
+                        .map_err(|e| format!("could not parse template YAML: {}", e))?;
+
+                    Document::Template(raw)
+                }
+                Some(_) => Document::Passthrough(value),
+            });
+        }
+
+        let mut params = HashMap::new();
+
+        for document in &documents {
+            let raw = match document {
+                Document::Template(raw) => raw,
+                Document::Passthrough(_) => continue,
+            };
+
+            for parameter in &raw.parameters {
+                if params.contains_key(&parameter.name) {
+                    continue;
+                }
+
+                let resolved = match parameter_values.get(&parameter.name) {
+                    Some(ParameterValue::Plain(v)) => Some(v.clone()),
+                    Some(ParameterValue::List(_)) | Some(ParameterValue::Map(_))
+                        if mode == RenderMode::Classic =>
+                    {
+                        return Err(format!(
+                            "parameter `{}` has a list or map value and cannot be used with \
+                             $(...) substitution",
+                            parameter.name
+                        ));
+                    }
+                    // In `RenderMode::Jinja`, list/map parameters were already expanded by the
+                    // MiniJinja pass, so there is nothing left for $(...) substitution to do.
+                    Some(ParameterValue::List(_)) | Some(ParameterValue::Map(_)) => continue,
+                    None => parameter.value.clone(),
+                };
+
+                match resolved {
+                    Some(v) => {
+                        params.insert(parameter.name.clone(), v);
+                    }
+                    None if parameter.required => {
+                        return Err(format!(
+                            "no value supplied for required parameter `{}`",
+                            parameter.name
+                        ));
+                    }
+                    None => {
+                        params.insert(parameter.name.clone(), String::new());
+                    }
+                }
+            }
+        }
+
+        Ok(Template {
+            documents,
+            params,
+            secrets: secrets.unwrap_or_default(),
+            secret_providers: SecretProviders::new(),
+        })
+    }
+
+    /// Returns the `parameters:` declared by `contents`, without otherwise parsing or rendering
+    /// the template.
+    ///
+    /// Unlike [`Template::new`] and friends, this doesn't require `parameter_values` up front, so
+    /// it lets a caller discover a template's declared parameter names (e.g. to feed
+    /// [`parameter_values_from_sources`](crate::parameter_values_from_sources)) before it has
+    /// values to resolve them with.
+    pub fn declared_parameters(contents: &str) -> Vec<Parameter> {
+        scan_parameters(contents)
+    }
+
+    /// Registers `providers` to resolve `scheme://reference` parameter and `data`/`stringData`
+    /// values at process time, instead of requiring the real value to be inlined.
+    pub fn with_secret_providers(mut self, providers: SecretProviders) -> Template {
+        self.secret_providers = providers;
+
+        self
+    }
+
+    /// Renders every document into a single `---`-delimited YAML document, preserving the
+    /// original document order.
+    pub fn process(&self) -> Result<ProcessedTemplate, String> {
+        let params = self.resolve_param_refs()?;
+        let mut remaining_secrets = self.secrets.clone();
+        let mut rendered = Vec::new();
+
+        for document in &self.documents {
+            match document {
+                Document::Template(raw) => {
+                    for object in &raw.objects {
+                        let object = substitute_tokens(object, &params)?;
+                        let object = resolve_secret_provider_refs(&object, &self.secret_providers)?;
+                        let object = self.encode_if_secret(object, &mut remaining_secrets)?;
+
+                        rendered.push(render_object(&object)?);
+                    }
+                }
+                Document::Passthrough(value) => rendered.push(render_object(value)?),
+            }
+        }
+
+        if let Some(secret) = remaining_secrets.iter().next() {
+            return Err(format!(
+                "secret `{}` was not found among the template's objects",
+                secret.name
+            ));
+        }
+
+        Ok(ProcessedTemplate(rendered.join("")))
+    }
+
+    /// Resolves any `scheme://reference` parameter values through the registered secret
+    /// providers, leaving plain values untouched.
+    fn resolve_param_refs(&self) -> Result<HashMap<String, String>, String> {
+        self.params
+            .iter()
+            .map(|(name, value)| {
+                let resolved = self
+                    .secret_providers
+                    .resolve(value)?
+                    .unwrap_or_else(|| value.clone());
+
+                Ok((name.clone(), resolved))
+            })
+            .collect()
+    }
+
+    fn encode_if_secret(&self, object: Value, remaining: &mut Secrets) -> Result<Value, String> {
+        let is_secret = object.get("kind").and_then(Value::as_str) == Some("Secret");
+
+        if !is_secret {
+            return Ok(object);
+        }
+
+        let metadata = object.get("metadata");
+        let name = metadata
+            .and_then(|m| m.get("name"))
+            .and_then(Value::as_str)
+            .map(str::to_string);
+        let namespace = metadata
+            .and_then(|m| m.get("namespace"))
+            .and_then(Value::as_str)
+            .unwrap_or("default")
+            .to_string();
+
+        let matched = name.as_ref().and_then(|name| {
+            remaining
+                .iter()
+                .find(|s| &s.name == name && s.namespace == namespace)
+                .cloned()
+        });
+
+        let matched = match matched {
+            Some(secret) => secret,
+            None => return Ok(object),
+        };
+
+        remaining.remove(&matched);
+
+        let mut object = object;
+
+        if let Value::Mapping(ref mut map) = object {
+            if let Some(data) = map.get(Value::from("data")).cloned() {
+                map.insert(Value::from("data"), encode_secret_data(&data));
+            }
+        }
+
+        Ok(object)
+    }
+}
+
+/// The rendered output of [`Template::process`]: a `---`-delimited YAML document.
+///
+/// Derefs to `str`, so it can be used anywhere a rendered template string was used before. With
+/// the `apply` feature enabled, [`ProcessedTemplate::apply`](crate::apply) can also send it
+/// straight to a cluster instead of requiring it to be piped to `kubectl`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ProcessedTemplate(String);
+
+impl std::ops::Deref for ProcessedTemplate {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Display for ProcessedTemplate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+fn render_object(object: &Value) -> Result<String, String> {
+    let rendered =
+        serde_yaml::to_string(object).map_err(|e| format!("could not serialize object: {}", e))?;
+    let body = rendered.strip_prefix("---\n").unwrap_or(&rendered);
+
+    Ok(format!("---\n{}", body))
+}
+
+fn render_jinja(contents: &str, parameter_values: &ParameterValues) -> Result<String, String> {
+    let defaults = scan_parameter_defaults(contents);
+
+    let dollar_params: HashMap<String, String> = defaults
+        .iter()
+        .map(|(name, value)| (name.clone(), value.clone()))
+        .chain(parameter_values.iter().filter_map(|(name, value)| match value {
+            ParameterValue::Plain(v) => Some((name.clone(), v.clone())),
+            ParameterValue::List(_) | ParameterValue::Map(_) => None,
+        }))
+        .collect();
+    let contents = substitute_str(contents, &dollar_params)?;
+
+    let mut env = Environment::new();
+
+    env.set_undefined_behavior(UndefinedBehavior::Strict);
+    env.add_filter("b64encode", |s: String| {
+        use base64::{engine::general_purpose::STANDARD, Engine};
+
+        STANDARD.encode(s)
+    });
+
+    let context = jinja_context(parameter_values, &defaults);
+
+    env.render_str(&contents, context)
+        .map_err(|e| format!("could not render template: {}", e))
+}
+
+/// Scans the raw, not-yet-rendered template text for `parameters:` declarations and returns the
+/// first declaration of each uniquely named parameter, in document order.
+///
+/// This only looks at `parameters:` blocks via a targeted regex rather than fully parsing the
+/// document, so it works even when the rest of the document — which may still contain unrendered
+/// `{% %}` control flow — isn't valid YAML yet.
+fn scan_parameters(contents: &str) -> Vec<Parameter> {
+    #[derive(Deserialize)]
+    struct ParametersBlock {
+        #[serde(default)]
+        parameters: Vec<Parameter>,
+    }
+
+    let block_re = Regex::new(r"(?m)^parameters:[ \t]*\r?\n((?:[ \t]+\S[^\n]*\n?|[ \t]*\n)*)")
+        .expect("invalid parameters block regex");
+
+    let mut seen = std::collections::HashSet::new();
+    let mut parameters = Vec::new();
+
+    for captures in block_re.captures_iter(contents) {
+        let yaml = format!("parameters:\n{}", &captures[1]);
+
+        let block: ParametersBlock = match serde_yaml::from_str(&yaml) {
+            Ok(block) => block,
+            Err(_) => continue,
+        };
+
+        for parameter in block.parameters {
+            if seen.insert(parameter.name.clone()) {
+                parameters.push(parameter);
+            }
+        }
+    }
+
+    parameters
+}
+
+/// Returns the default `value:` of every parameter declared in `contents` that has one, keyed by
+/// name.
+///
+/// This runs before MiniJinja sees the template, since a parameter relying on its own `value:`
+/// default (rather than being passed explicitly) must still resolve under
+/// [`UndefinedBehavior::Strict`] — otherwise `{{ NAME }}` would hard-error even though the
+/// equivalent `$(NAME)` substitution (which consults the same default, after full YAML parsing)
+/// succeeds.
+fn scan_parameter_defaults(contents: &str) -> HashMap<String, String> {
+    scan_parameters(contents)
+        .into_iter()
+        .filter_map(|parameter| {
+            let name = parameter.name;
+
+            parameter.value.map(|value| (name, value))
+        })
+        .collect()
+}
+
+fn jinja_context(parameter_values: &ParameterValues, defaults: &HashMap<String, String>) -> JinjaValue {
+    let mut context = HashMap::new();
+
+    for (name, value) in defaults {
+        context.insert(name.clone(), JinjaValue::from(value.clone()));
+    }
+
+    for (name, value) in parameter_values {
+        let value = match value {
+            ParameterValue::Plain(v) => JinjaValue::from(v.clone()),
+            ParameterValue::List(items) => JinjaValue::from(items.clone()),
+            ParameterValue::Map(map) => JinjaValue::from(map.clone()),
+        };
+
+        context.insert(name.clone(), value);
+    }
+
+    JinjaValue::from(context)
+}